@@ -1,16 +1,205 @@
 use std::{
+    cell::RefCell,
     error::Error,
     fmt::Display,
     io::{Read, stdin},
+    path::PathBuf,
+    rc::Rc,
 };
 
 use clap::{Args, Parser, Subcommand, arg, command};
-use gcode::{Callbacks, GCode, Line, Mnemonic, Span, Word, full_parse_with_callbacks};
+use gcode::{
+    Callbacks, Comment, GCode, Line as GcodeLine, Mnemonic, Span, Word, full_parse_with_callbacks,
+};
 use serde::{Deserialize, Serialize};
 
-struct GcodeError;
+/// A comment recovered from the raw source, keeping enough of its original
+/// form (`(...)` vs `;...`) that re-emitting it doesn't change its kind.
+#[derive(Debug, Clone)]
+enum LineComment {
+    Parenthetical(String),
+    LineEnd(String),
+}
+
+impl Display for LineComment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineComment::Parenthetical(text) => write!(f, "({text})"),
+            LineComment::LineEnd(text) => write!(f, ";{text}"),
+        }
+    }
+}
+
+/// Recover `gcode::Line`'s comments without merging them: each stays a
+/// separate entry and keeps its original delimiter kind, so re-emitting them is a no-op instead
+/// of a lossy paraphrase.
+fn line_comments(comments: &[Comment<'_>]) -> Vec<LineComment> {
+    comments
+        .iter()
+        .map(|comment| {
+            let value = comment.value;
+            if let Some(text) = value.strip_prefix(';') {
+                LineComment::LineEnd(text.to_string())
+            } else {
+                LineComment::Parenthetical(
+                    value.trim_matches(['(', ')']).to_string(),
+                )
+            }
+        })
+        .collect()
+}
+
+/// A single physical source line: the G-codes parsed from it, plus any
+/// line number and comments recovered from the raw source. Keeping all of
+/// these lets `print_lines` round-trip a file losslessly instead of only
+/// printing `gcode::Line`'s parsed commands.
+struct Line {
+    line_number: Option<u32>,
+    gcodes: Vec<GCode>,
+    comments: Vec<LineComment>,
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut wrote_anything = false;
+        if let Some(line_number) = self.line_number {
+            write!(f, "N{line_number}")?;
+            wrote_anything = true;
+        }
+        for command in &self.gcodes {
+            if wrote_anything {
+                write!(f, " ")?;
+            }
+            write!(f, "{command}")?;
+            wrote_anything = true;
+        }
+        for comment in &self.comments {
+            if wrote_anything {
+                write!(f, " ")?;
+            }
+            write!(f, "{comment}")?;
+            wrote_anything = true;
+        }
+        Ok(())
+    }
+}
 
-impl Callbacks for GcodeError {} // TODO
+/// A parse-time problem that `full_parse_with_callbacks` noticed but, absent
+/// a real `Callbacks` impl, would otherwise drop on the floor.
+#[derive(Debug, Clone)]
+enum Diagnostic {
+    UnknownContent { text: String, line: usize },
+    UnexpectedLineNumber { line_number: f32, line: usize },
+    ArgumentWithoutCommand { letter: char, value: f32, line: usize },
+    MalformedNumber { text: String, line: usize },
+    MalformedWord { text: String, line: usize },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::UnknownContent { text, line } => {
+                write!(f, "line {line}: ignored unrecognized content {text:?}")
+            }
+            Diagnostic::UnexpectedLineNumber { line_number, line } => {
+                write!(f, "line {line}: unexpected line number N{line_number}")
+            }
+            Diagnostic::ArgumentWithoutCommand { letter, value, line } => write!(
+                f,
+                "line {line}: argument {letter}{value} has no command to attach to"
+            ),
+            Diagnostic::MalformedNumber { text, line } => {
+                write!(f, "line {line}: couldn't parse number {text:?}")
+            }
+            Diagnostic::MalformedWord { text, line } => {
+                write!(f, "line {line}: couldn't parse word {text:?}")
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct DiagnosticsInner {
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Collects parse diagnostics that `full_parse_with_callbacks` would
+/// otherwise discard. Shares a handle via `Rc<RefCell<_>>` so the caller can
+/// read the results back out after the crate has consumed its own clone.
+#[derive(Clone)]
+struct Diagnostics {
+    source: Rc<str>,
+    inner: Rc<RefCell<DiagnosticsInner>>,
+}
+
+impl Diagnostics {
+    fn new(source: &str) -> Diagnostics {
+        Diagnostics {
+            source: Rc::from(source),
+            inner: Rc::new(RefCell::new(DiagnosticsInner::default())),
+        }
+    }
+
+    /// 0-based index of the physical source line containing `span`, matching
+    /// the indexing `full_parse_with_callbacks` uses for its `Line`s.
+    fn line_index(&self, span: Span) -> usize {
+        self.source[..span.start].matches('\n').count()
+    }
+
+    fn take_diagnostics(&self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.inner.borrow_mut().diagnostics)
+    }
+}
+
+impl Callbacks for Diagnostics {
+    fn unknown_content(&mut self, text: &str, span: Span) {
+        // Note: real `(...)`/`;...` comments never reach this callback; the
+        // parser recognizes them on its own and attaches them to the
+        // enclosing `gcode::Line` instead (see `line_comments`). Whatever
+        // lands here is genuinely unrecognized source text.
+        let line = self.line_index(span) + 1;
+        self.inner
+            .borrow_mut()
+            .diagnostics
+            .push(Diagnostic::UnknownContent {
+                text: text.to_string(),
+                line,
+            });
+    }
+
+    fn unexpected_line_number(&mut self, line_number: f32, span: Span) {
+        let line = self.line_index(span) + 1;
+        self.inner
+            .borrow_mut()
+            .diagnostics
+            .push(Diagnostic::UnexpectedLineNumber { line_number, line });
+    }
+
+    fn argument_without_a_command(&mut self, letter: char, value: f32, span: Span) {
+        let line = self.line_index(span) + 1;
+        self.inner.borrow_mut().diagnostics.push(Diagnostic::ArgumentWithoutCommand {
+            letter,
+            value,
+            line,
+        });
+    }
+
+    fn number_without_a_letter(&mut self, value: &str, span: Span) {
+        let line = self.line_index(span) + 1;
+        self.inner.borrow_mut().diagnostics.push(Diagnostic::MalformedNumber {
+            text: value.to_string(),
+            line,
+        });
+    }
+
+    fn letter_without_a_number(&mut self, value: &str, span: Span) {
+        let line = self.line_index(span) + 1;
+        self.inner.borrow_mut().diagnostics.push(Diagnostic::MalformedWord {
+            text: value.to_string(),
+            line,
+        });
+    }
+}
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct Extent {
@@ -18,6 +207,7 @@ struct Extent {
     min_y: f32,
     max_x: f32,
     max_y: f32,
+    units: Units,
 }
 
 #[derive(Debug)]
@@ -25,6 +215,9 @@ enum GctkError {
     UnsupportedCommand(GCode),
     EmptyExtent,
     UnknownPosition(usize),
+    NonUniformArcScale(GCode),
+    AmbiguousUnitsConversion(GCode),
+    EmptyMesh,
 }
 
 impl Display for GctkError {
@@ -41,6 +234,18 @@ impl Display for GctkError {
                 f,
                 "Found relative motion command with unknown absolute position on line number {line_num}"
             ),
+            GctkError::NonUniformArcScale(gcode) => write!(
+                f,
+                "Cannot scale arc {gcode} with different X and Y factors (it would turn a circle into an ellipse, which G-code can't express)"
+            ),
+            GctkError::AmbiguousUnitsConversion(gcode) => write!(
+                f,
+                "Found {gcode} sharing a line with a G20/G21 units change; its feed rate or arc offset would be ambiguous to convert (unclear which unit system it was written in)"
+            ),
+            GctkError::EmptyMesh => write!(
+                f,
+                "Found empty probe mesh (leveling needs at least one probe point to interpolate an offset from)"
+            ),
         }
     }
 }
@@ -52,10 +257,97 @@ enum PositioningMode {
     Relative,
 }
 
+/// Active unit system, set by G20 (inch) / G21 (mm). Defaults to millimeters,
+/// the more common machine default and the unit `get_xy_extent` normalizes to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Units {
+    #[default]
+    Millimeters,
+    Inches,
+}
+
+impl Units {
+    /// Conversion factor to millimeters (1 inch = 25.4 mm).
+    fn per_mm(self) -> f32 {
+        match self {
+            Units::Millimeters => 1.,
+            Units::Inches => 25.4,
+        }
+    }
+}
+
+/// Convert `value` from `from` units into `to` units.
+fn convert_value(value: f32, from: Units, to: Units) -> f32 {
+    value * from.per_mm() / to.per_mm()
+}
+
+/// Advance `position` to `value` (absolute or relative, per `mode`) and fold
+/// the result into `min`/`max`. Returns `UnknownPosition` for a relative move
+/// before any absolute reference has been seen on this axis.
+fn advance_and_bound(
+    mode: &PositioningMode,
+    position: &mut f32,
+    known: &mut bool,
+    min: &mut Option<f32>,
+    max: &mut Option<f32>,
+    value: f32,
+    line_idx: usize,
+) -> Result<(), GctkError> {
+    match mode {
+        PositioningMode::Absolute => *position = value,
+        PositioningMode::Relative => {
+            if !*known {
+                return Err(GctkError::UnknownPosition(line_idx + 1));
+            }
+            *position += value;
+        }
+    }
+    *known = true;
+    *min = Some(min.map_or(*position, |m| m.min(*position)));
+    *max = Some(max.map_or(*position, |m| m.max(*position)));
+    Ok(())
+}
+
+/// Fold a point that isn't the machine's new position (e.g. an arc's
+/// extreme compass point) into `min`/`max`.
+fn bound(min: &mut Option<f32>, max: &mut Option<f32>, value: f32) {
+    *min = Some(min.map_or(value, |m| m.min(value)));
+    *max = Some(max.map_or(value, |m| m.max(value)));
+}
+
+/// Radians swept from `start_angle` to `end_angle` going clockwise or
+/// counterclockwise, in `(0, tau]`; a zero sweep between identical angles
+/// (I/J form with no X/Y change) means a full circle.
+fn arc_sweep(start_angle: f32, end_angle: f32, clockwise: bool) -> f32 {
+    let tau = std::f32::consts::TAU;
+    let raw = if clockwise {
+        start_angle - end_angle
+    } else {
+        end_angle - start_angle
+    };
+    let sweep = raw.rem_euclid(tau);
+    if sweep == 0. { tau } else { sweep }
+}
+
+/// Whether the compass angle `point_angle` falls within the arc swept from
+/// `start_angle` by `sweep` radians in the given direction.
+fn angle_in_sweep(start_angle: f32, sweep: f32, point_angle: f32, clockwise: bool) -> bool {
+    let tau = std::f32::consts::TAU;
+    let delta = if clockwise {
+        start_angle - point_angle
+    } else {
+        point_angle - start_angle
+    };
+    delta.rem_euclid(tau) <= sweep
+}
+
 fn get_xy_extent(lines: &[Line]) -> Result<Extent, GctkError> {
     let (mut min_x, mut min_y, mut max_x, mut max_y) = (None, None, None, None);
     let mut position = Point3::zero();
+    let (mut known_x, mut known_y) = (false, false);
     let mut positioning_mode = PositioningMode::Absolute;
+    let mut units = Units::default();
     for (line_idx, line) in lines.iter().enumerate() {
         for command in line
             .gcodes
@@ -65,81 +357,128 @@ fn get_xy_extent(lines: &[Line]) -> Result<Extent, GctkError> {
             match command.major_number() {
                 0 | 1 => {
                     if let Some(x) = command.value_for('X') {
-                        match (&positioning_mode, min_x) {
-                            (PositioningMode::Absolute, Some(m)) => {
-                                if x < m {
-                                    min_x = Some(x)
-                                }
-                            }
-                            (PositioningMode::Relative, Some(m)) => {
-                                position.x += x;
-                                if position.x < m {
-                                    min_x = Some(position.x);
-                                }
-                            }
-                            (PositioningMode::Absolute, None) => min_x = Some(x),
-                            (PositioningMode::Relative, None) => {
-                                return Err(GctkError::UnknownPosition(line_idx + 1));
-                            }
-                        }
-                        match (&positioning_mode, max_x) {
-                            (PositioningMode::Absolute, Some(m)) => {
-                                if x > m {
-                                    max_x = Some(x)
-                                }
-                            }
-                            (PositioningMode::Relative, Some(m)) => {
-                                position.x += x;
-                                if position.x > m {
-                                    max_x = Some(position.x);
-                                }
-                            }
-                            (PositioningMode::Absolute, None) => max_x = Some(x),
-                            (PositioningMode::Relative, None) => {
-                                return Err(GctkError::UnknownPosition(line_idx + 1));
-                            }
-                        }
+                        advance_and_bound(
+                            &positioning_mode,
+                            &mut position.x,
+                            &mut known_x,
+                            &mut min_x,
+                            &mut max_x,
+                            convert_value(x, units, Units::Millimeters),
+                            line_idx,
+                        )?;
                     }
                     if let Some(y) = command.value_for('Y') {
-                        match (&positioning_mode, min_y) {
-                            (PositioningMode::Absolute, Some(m)) => {
-                                if y < m {
-                                    min_y = Some(y)
-                                }
-                            }
-                            (PositioningMode::Relative, Some(m)) => {
-                                position.y += y;
-                                if position.y < m {
-                                    min_y = Some(position.y);
-                                }
-                            }
-                            (PositioningMode::Absolute, None) => min_y = Some(y),
-                            (PositioningMode::Relative, None) => {
-                                return Err(GctkError::UnknownPosition(line_idx + 1));
-                            }
+                        advance_and_bound(
+                            &positioning_mode,
+                            &mut position.y,
+                            &mut known_y,
+                            &mut min_y,
+                            &mut max_y,
+                            convert_value(y, units, Units::Millimeters),
+                            line_idx,
+                        )?;
+                    }
+                }
+                major @ (2 | 3) => {
+                    if !known_x || !known_y {
+                        return Err(GctkError::UnknownPosition(line_idx + 1));
+                    }
+                    let clockwise = major == 2;
+                    let (start_x, start_y) = (position.x, position.y);
+
+                    if let Some(x) = command.value_for('X') {
+                        advance_and_bound(
+                            &positioning_mode,
+                            &mut position.x,
+                            &mut known_x,
+                            &mut min_x,
+                            &mut max_x,
+                            convert_value(x, units, Units::Millimeters),
+                            line_idx,
+                        )?;
+                    }
+                    if let Some(y) = command.value_for('Y') {
+                        advance_and_bound(
+                            &positioning_mode,
+                            &mut position.y,
+                            &mut known_y,
+                            &mut min_y,
+                            &mut max_y,
+                            convert_value(y, units, Units::Millimeters),
+                            line_idx,
+                        )?;
+                    }
+                    let (end_x, end_y) = (position.x, position.y);
+
+                    let (center_x, center_y) = if let (Some(i), Some(j)) =
+                        (command.value_for('I'), command.value_for('J'))
+                    {
+                        let i = convert_value(i, units, Units::Millimeters);
+                        let j = convert_value(j, units, Units::Millimeters);
+                        (start_x + i, start_y + j)
+                    } else if let Some(r) = command.value_for('R') {
+                        let r = convert_value(r, units, Units::Millimeters);
+                        let dx = end_x - start_x;
+                        let dy = end_y - start_y;
+                        let chord = (dx * dx + dy * dy).sqrt();
+                        let radius = r.abs();
+                        let half_height = (radius * radius - (chord / 2.) * (chord / 2.))
+                            .max(0.)
+                            .sqrt();
+                        let mid_x = (start_x + end_x) / 2.;
+                        let mid_y = (start_y + end_y) / 2.;
+                        let (ux, uy) = if chord == 0. {
+                            (0., 0.)
+                        } else {
+                            (-dy / chord, dx / chord)
+                        };
+                        // A positive R takes the short way around (<=180deg); a
+                        // negative R takes the long way. Either sign picks
+                        // between the two candidate centers on either side of
+                        // the chord.
+                        let short_way = r >= 0.;
+                        let on_left = clockwise != short_way;
+                        if on_left {
+                            (mid_x + half_height * ux, mid_y + half_height * uy)
+                        } else {
+                            (mid_x - half_height * ux, mid_y - half_height * uy)
                         }
-                        match (&positioning_mode, max_y) {
-                            (PositioningMode::Absolute, Some(m)) => {
-                                if y > m {
-                                    max_y = Some(y)
-                                }
-                            }
-                            (PositioningMode::Relative, Some(m)) => {
-                                position.y += y;
-                                if position.y > m {
-                                    max_y = Some(position.y);
-                                }
-                            }
-                            (PositioningMode::Absolute, None) => max_y = Some(y),
-                            (PositioningMode::Relative, None) => {
-                                return Err(GctkError::UnknownPosition(line_idx + 1));
-                            }
+                    } else {
+                        return Err(GctkError::UnsupportedCommand(command.clone()));
+                    };
+
+                    let radius_start =
+                        ((start_x - center_x).powi(2) + (start_y - center_y).powi(2)).sqrt();
+                    let radius_end =
+                        ((end_x - center_x).powi(2) + (end_y - center_y).powi(2)).sqrt();
+                    let radius = radius_start.max(radius_end);
+                    let start_angle = (start_y - center_y).atan2(start_x - center_x);
+                    let end_angle = (end_y - center_y).atan2(end_x - center_x);
+                    let sweep = arc_sweep(start_angle, end_angle, clockwise);
+
+                    bound(&mut min_x, &mut max_x, start_x);
+                    bound(&mut min_y, &mut max_y, start_y);
+                    bound(&mut min_x, &mut max_x, end_x);
+                    bound(&mut min_y, &mut max_y, end_y);
+
+                    let compass = [
+                        (0., center_x + radius, center_y),
+                        (std::f32::consts::FRAC_PI_2, center_x, center_y + radius),
+                        (std::f32::consts::PI, center_x - radius, center_y),
+                        (3. * std::f32::consts::FRAC_PI_2, center_x, center_y - radius),
+                    ];
+                    for (angle, x, y) in compass {
+                        if angle_in_sweep(start_angle, sweep, angle, clockwise) {
+                            bound(&mut min_x, &mut max_x, x);
+                            bound(&mut min_y, &mut max_y, y);
                         }
                     }
                 }
                 90 => positioning_mode = PositioningMode::Absolute,
                 91 => positioning_mode = PositioningMode::Relative,
-                4 | 21 | 64 | 94 => (),
+                20 => units = Units::Inches,
+                21 => units = Units::Millimeters,
+                4 | 64 | 94 => (),
                 _ => return Err(GctkError::UnsupportedCommand(command.clone())),
             };
         }
@@ -149,6 +488,7 @@ fn get_xy_extent(lines: &[Line]) -> Result<Extent, GctkError> {
         min_y: min_y.ok_or(GctkError::EmptyExtent)?,
         max_x: max_x.ok_or(GctkError::EmptyExtent)?,
         max_y: max_y.ok_or(GctkError::EmptyExtent)?,
+        units: Units::Millimeters,
     })
 }
 
@@ -168,7 +508,11 @@ impl Point3 {
     }
 }
 
-fn translate(lines: &mut [Line], offset: &Point3) -> Result<(), GctkError> {
+/// `offset` is given in `offset_units`; it's converted into each command's
+/// currently active units (tracked via G20/G21) before being applied, so a
+/// fixed `--x`/`--y`/`--z` reads correctly across files that switch units.
+fn translate(lines: &mut [Line], offset: &Point3, offset_units: Units) -> Result<(), GctkError> {
+    let mut units = Units::default();
     for line in lines.iter_mut() {
         for command in line
             .gcodes
@@ -176,17 +520,22 @@ fn translate(lines: &mut [Line], offset: &Point3) -> Result<(), GctkError> {
             .filter(|c| c.mnemonic == Mnemonic::General)
         {
             match command.major_number() {
-                0 | 1 | 2 => {
+                0..=3 => {
+                    let offset_x = convert_value(offset.x, offset_units, units);
+                    let offset_y = convert_value(offset.y, offset_units, units);
+                    let offset_z = convert_value(offset.z, offset_units, units);
                     for argument in command.arguments.iter_mut() {
                         match argument.letter.to_ascii_uppercase() {
-                            'X' => argument.value += offset.x,
-                            'Y' => argument.value += offset.y,
-                            'Z' => argument.value += offset.z,
+                            'X' => argument.value += offset_x,
+                            'Y' => argument.value += offset_y,
+                            'Z' => argument.value += offset_z,
                             _ => (),
                         }
                     }
                 }
-                4 | 21 | 64 | 90 | 91 | 94 => (),
+                20 => units = Units::Inches,
+                21 => units = Units::Millimeters,
+                4 | 64 | 90 | 91 | 94 => (),
                 _ => return Err(GctkError::UnsupportedCommand(command.clone())),
             };
         }
@@ -210,7 +559,15 @@ impl From<&MirrorAxis> for char {
     }
 }
 
-fn mirror(lines: &mut [Line], axis: MirrorAxis, value: f32) -> Result<(), GctkError> {
+/// `value` is given in `offset_units`; it's converted into each command's
+/// currently active units (tracked via G20/G21) before mirroring about it.
+fn mirror(
+    lines: &mut [Line],
+    axis: MirrorAxis,
+    value: f32,
+    offset_units: Units,
+) -> Result<(), GctkError> {
+    let mut units = Units::default();
     for line in lines.iter_mut() {
         for command in line
             .gcodes
@@ -219,13 +576,15 @@ fn mirror(lines: &mut [Line], axis: MirrorAxis, value: f32) -> Result<(), GctkEr
         {
             match command.major_number() {
                 0 | 1 => {
+                    let value = convert_value(value, offset_units, units);
                     for argument in command.arguments.iter_mut() {
                         if argument.letter == (&axis).into() {
                             argument.value = 2. * value - argument.value;
                         }
                     }
                 }
-                2 => {
+                2 | 3 => {
+                    let value = convert_value(value, offset_units, units);
                     for argument in command.arguments.iter_mut() {
                         match (argument.letter, &axis) {
                             (l, a) if l == a.into() => argument.value = 2. * value - argument.value,
@@ -243,7 +602,9 @@ fn mirror(lines: &mut [Line], axis: MirrorAxis, value: f32) -> Result<(), GctkEr
                         }
                     }
                 }
-                4 | 21 | 64 | 90 | 94 => (),
+                20 => units = Units::Inches,
+                21 => units = Units::Millimeters,
+                4 | 64 | 90 | 94 => (),
                 _ => return Err(GctkError::UnsupportedCommand(command.clone())),
             };
         }
@@ -251,12 +612,11 @@ fn mirror(lines: &mut [Line], axis: MirrorAxis, value: f32) -> Result<(), GctkEr
     Ok(())
 }
 
-type _Mesh = Vec<Point3>;
-
-fn _mesh_level(lines: &mut [Line], _mesh: _Mesh, _num_neighbors: usize) -> Result<(), GctkError> {
-    let mut current_x = None;
-    let mut current_y = None;
-    let mut current_z = None;
+/// Multiply every coordinate word by the matching axis factor. G90/G91 are
+/// passed through untouched: scaling a relative (delta) move by a factor
+/// gives the same result as scaling the absolute move it's relative to, so
+/// no positioning-mode bookkeeping is needed here.
+fn scale(lines: &mut [Line], factors: &Point3) -> Result<(), GctkError> {
     for line in lines.iter_mut() {
         for command in line
             .gcodes
@@ -265,45 +625,319 @@ fn _mesh_level(lines: &mut [Line], _mesh: _Mesh, _num_neighbors: usize) -> Resul
         {
             match command.major_number() {
                 0 | 1 => {
-                    let (mut command_x, mut command_y, mut command_z) = (None, None, None);
-                    for argument in command.arguments.iter() {
-                        match argument.letter {
-                            'X' => command_x = Some(argument.value),
-                            'Y' => command_y = Some(argument.value),
-                            'Z' => command_z = Some(argument.value),
+                    for argument in command.arguments.iter_mut() {
+                        match argument.letter.to_ascii_uppercase() {
+                            'X' => argument.value *= factors.x,
+                            'Y' => argument.value *= factors.y,
+                            'Z' => argument.value *= factors.z,
                             _ => (),
-                        };
-                    }
-                    if command_x.is_some() {
-                        current_x = command_x;
+                        }
                     }
-                    if command_y.is_some() {
-                        current_y = command_y;
+                }
+                2 | 3 => {
+                    if factors.x != factors.y {
+                        return Err(GctkError::NonUniformArcScale(command.clone()));
                     }
-                    if command_z.is_some() {
-                        current_z = command_z;
+                    for argument in command.arguments.iter_mut() {
+                        match argument.letter.to_ascii_uppercase() {
+                            'X' | 'I' => argument.value *= factors.x,
+                            'Y' | 'J' => argument.value *= factors.y,
+                            'Z' => argument.value *= factors.z,
+                            'R' => argument.value *= factors.x,
+                            _ => (),
+                        }
                     }
-                    if current_x.is_some() && current_y.is_some() && current_z.is_some() {
-                        let (_x, _y, mut new_z) =
-                            (current_x.unwrap(), current_y.unwrap(), current_z.unwrap());
-                        new_z *= -1.; // TODO estimate adjusted Z value using mesh values and current position
-                        if command_z.is_some() {
-                            for argument in command.arguments.iter_mut() {
-                                if let 'Z' = argument.letter {
-                                    argument.value = new_z;
+                }
+                4 | 20 | 21 | 64 | 90 | 91 | 94 => (),
+                _ => return Err(GctkError::UnsupportedCommand(command.clone())),
+            };
+        }
+    }
+    Ok(())
+}
+
+/// A single probed height at `(x, y)`, relative to the machine's reference
+/// plane. Mirrors the plain-f32-fields style of [`Extent`] so mesh files can
+/// be hand-written or dumped straight from a probing routine.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+struct ProbePoint {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+type Mesh = Vec<ProbePoint>;
+
+/// Set an existing argument's value, or append it if the command doesn't
+/// already carry that letter.
+fn set_argument(command: &mut GCode, letter: char, value: f32) {
+    for argument in command.arguments.iter_mut() {
+        if argument.letter == letter {
+            argument.value = value;
+            return;
+        }
+    }
+    command
+        .push_argument(Word::new(letter, value, Span::PLACEHOLDER))
+        .unwrap();
+}
+
+/// Inverse-distance-weighted height offset at `(x, y)` over the
+/// `num_neighbors` closest probe points, with weights `1 / d^power`. Snaps to
+/// an exact probe value when `(x, y)` lands on it to avoid dividing by zero.
+fn mesh_offset(mesh: &Mesh, x: f32, y: f32, num_neighbors: usize, power: f32) -> f32 {
+    let mut by_distance: Vec<(f32, f32)> = mesh
+        .iter()
+        .map(|probe| {
+            let dx = probe.x - x;
+            let dy = probe.y - y;
+            ((dx * dx + dy * dy).sqrt(), probe.z)
+        })
+        .collect();
+    by_distance.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    by_distance.truncate(num_neighbors.max(1));
+
+    if let Some(&(_, z)) = by_distance.iter().find(|(d, _)| *d == 0.) {
+        return z;
+    }
+
+    let mut weight_sum = 0.;
+    let mut weighted_z_sum = 0.;
+    for (d, z) in by_distance {
+        let weight = 1. / d.powf(power);
+        weight_sum += weight;
+        weighted_z_sum += weight * z;
+    }
+    weighted_z_sum / weight_sum
+}
+
+/// Compensate every G0/G1 move's Z for bed warp described by `mesh`, whose
+/// points are always in millimeters regardless of the file's active
+/// G20/G21 units. Moves longer than `segment_length` in XY are split into
+/// colinear sub-moves so the toolpath actually follows the interpolated
+/// surface instead of just correcting its endpoints.
+fn mesh_level(
+    lines: &mut [Line],
+    mesh: &Mesh,
+    num_neighbors: usize,
+    power: f32,
+    segment_length: f32,
+) -> Result<(), GctkError> {
+    if mesh.is_empty() {
+        return Err(GctkError::EmptyMesh);
+    }
+
+    let mut position_x = None;
+    let mut position_y = None;
+    let mut position_z = None;
+    let mut positioning_mode = PositioningMode::Absolute;
+    // The Z actually emitted for the previous sub-move (nominal Z plus mesh
+    // offset), as opposed to `position_z`'s nominal value. G91 moves need
+    // their delta measured against this, not against the nominal path.
+    let mut emitted_z = 0.;
+    // `mesh` is probed in millimeters regardless of the file's active
+    // G20/G21 units, so XY has to be converted in before looking up an
+    // offset and the offset converted back out before being added to Z.
+    let mut units = Units::default();
+
+    for (line_idx, line) in lines.iter_mut().enumerate() {
+        let old_gcodes = std::mem::take(&mut line.gcodes);
+        let mut new_gcodes = Vec::with_capacity(old_gcodes.len());
+
+        for command in old_gcodes {
+            if command.mnemonic != Mnemonic::General {
+                new_gcodes.push(command);
+                continue;
+            }
+            match command.major_number() {
+                0 | 1 => {
+                    let start_z = position_z.unwrap_or(0.);
+                    let command_x = command.value_for('X');
+                    let command_y = command.value_for('Y');
+                    let command_z = command.value_for('Z');
+
+                    // Matches `start_z`: a never-before-seen X/Y defaults to
+                    // 0 rather than erroring, so a pure Z/F preamble move
+                    // (no X or Y word at all) doesn't need a
+                    // position-setting move before it. A relative move that
+                    // actually carries an X/Y word still needs a known prior
+                    // position to measure its delta from.
+                    let start_x = position_x.unwrap_or(0.);
+                    let start_y = position_y.unwrap_or(0.);
+
+                    let end_x = match (&positioning_mode, command_x) {
+                        (_, None) => position_x.unwrap_or(start_x),
+                        (PositioningMode::Absolute, Some(x)) => x,
+                        (PositioningMode::Relative, Some(x)) => {
+                            position_x.ok_or(GctkError::UnknownPosition(line_idx + 1))? + x
+                        }
+                    };
+                    let end_y = match (&positioning_mode, command_y) {
+                        (_, None) => position_y.unwrap_or(start_y),
+                        (PositioningMode::Absolute, Some(y)) => y,
+                        (PositioningMode::Relative, Some(y)) => {
+                            position_y.ok_or(GctkError::UnknownPosition(line_idx + 1))? + y
+                        }
+                    };
+                    let end_z = match (&positioning_mode, command_z) {
+                        (_, None) => start_z,
+                        (PositioningMode::Absolute, Some(z)) => z,
+                        (PositioningMode::Relative, Some(z)) => start_z + z,
+                    };
+
+                    let distance =
+                        ((end_x - start_x).powi(2) + (end_y - start_y).powi(2)).sqrt();
+                    let num_segments = if segment_length > 0. {
+                        (distance / segment_length).ceil().max(1.) as usize
+                    } else {
+                        1
+                    };
+
+                    // Only carry an X/Y word on the sub-moves if the source
+                    // command had one, or if we're actually interpolating
+                    // through several segments and need to say where each
+                    // one goes. A pure "G1 Z0.2" Z-hop must stay a pure Z
+                    // move; synthesizing X/Y here would smuggle an absolute
+                    // position into what may still be a relative command.
+                    let needs_x = command_x.is_some() || num_segments > 1;
+                    let needs_y = command_y.is_some() || num_segments > 1;
+                    let (mut prev_x, mut prev_y) = (start_x, start_y);
+
+                    for segment in 1..=num_segments {
+                        let t = segment as f32 / num_segments as f32;
+                        let sub_x = start_x + t * (end_x - start_x);
+                        let sub_y = start_y + t * (end_y - start_y);
+                        let probe_x = convert_value(sub_x, units, Units::Millimeters);
+                        let probe_y = convert_value(sub_y, units, Units::Millimeters);
+                        let offset_mm = mesh_offset(mesh, probe_x, probe_y, num_neighbors, power);
+                        let offset = convert_value(offset_mm, Units::Millimeters, units);
+                        let sub_z = start_z + t * (end_z - start_z) + offset;
+
+                        let mut sub_command = command.clone();
+                        match positioning_mode {
+                            PositioningMode::Absolute => {
+                                if needs_x {
+                                    set_argument(&mut sub_command, 'X', sub_x);
+                                }
+                                if needs_y {
+                                    set_argument(&mut sub_command, 'Y', sub_y);
                                 }
+                                set_argument(&mut sub_command, 'Z', sub_z);
+                            }
+                            PositioningMode::Relative => {
+                                if needs_x {
+                                    set_argument(&mut sub_command, 'X', sub_x - prev_x);
+                                }
+                                if needs_y {
+                                    set_argument(&mut sub_command, 'Y', sub_y - prev_y);
+                                }
+                                set_argument(&mut sub_command, 'Z', sub_z - emitted_z);
                             }
-                        } else {
-                            command
-                                .push_argument(Word::new('Z', new_z, Span::PLACEHOLDER))
-                                .unwrap();
                         }
+                        new_gcodes.push(sub_command);
+
+                        prev_x = sub_x;
+                        prev_y = sub_y;
+                        emitted_z = sub_z;
                     }
+
+                    position_x = Some(end_x);
+                    position_y = Some(end_y);
+                    position_z = Some(end_z);
                 }
-                4 | 21 | 64 | 90 | 94 => (),
-                _ => return Err(GctkError::UnsupportedCommand(command.clone())),
+                90 => {
+                    positioning_mode = PositioningMode::Absolute;
+                    new_gcodes.push(command);
+                }
+                91 => {
+                    positioning_mode = PositioningMode::Relative;
+                    new_gcodes.push(command);
+                }
+                20 => {
+                    units = Units::Inches;
+                    new_gcodes.push(command);
+                }
+                21 => {
+                    units = Units::Millimeters;
+                    new_gcodes.push(command);
+                }
+                4 | 64 | 94 => new_gcodes.push(command),
+                _ => return Err(GctkError::UnsupportedCommand(command)),
             };
         }
+
+        line.gcodes = new_gcodes;
+    }
+    Ok(())
+}
+
+/// Rewrite every coordinate word (and feed rate) by the 25.4 mm/in factor and
+/// flip each G20/G21 word to the other system, so the file describes the
+/// same physical toolpath in the opposite units. Errors out instead of
+/// guessing when a G20/G21 shares a physical line with a feed rate or arc
+/// offset, since it's then ambiguous whether that value was written in the
+/// old units or the new ones.
+fn convert_units(lines: &mut [Line]) -> Result<(), GctkError> {
+    let mut units = Units::default();
+    for line in lines.iter_mut() {
+        let has_units_change = line
+            .gcodes
+            .iter()
+            .any(|c| c.mnemonic == Mnemonic::General && matches!(c.major_number(), 20 | 21));
+        if has_units_change {
+            if let Some(ambiguous) = line.gcodes.iter().find(|c| {
+                c.mnemonic == Mnemonic::General
+                    && matches!(c.major_number(), 0..=3)
+                    && c.arguments
+                        .iter()
+                        .any(|a| matches!(a.letter.to_ascii_uppercase(), 'F' | 'I' | 'J' | 'R'))
+            }) {
+                return Err(GctkError::AmbiguousUnitsConversion(ambiguous.clone()));
+            }
+        }
+
+        let old_gcodes = std::mem::take(&mut line.gcodes);
+        let mut new_gcodes = Vec::with_capacity(old_gcodes.len());
+
+        for command in old_gcodes {
+            if command.mnemonic != Mnemonic::General {
+                new_gcodes.push(command);
+                continue;
+            }
+            match command.major_number() {
+                20 | 21 => {
+                    units = match command.major_number() {
+                        20 => Units::Inches,
+                        _ => Units::Millimeters,
+                    };
+                    let flipped_major = match units {
+                        Units::Millimeters => 20.,
+                        Units::Inches => 21.,
+                    };
+                    new_gcodes.push(GCode::new(Mnemonic::General, flipped_major, Span::PLACEHOLDER));
+                }
+                0..=3 => {
+                    let new_units = match units {
+                        Units::Millimeters => Units::Inches,
+                        Units::Inches => Units::Millimeters,
+                    };
+                    let mut converted = command.clone();
+                    for argument in converted.arguments.iter_mut() {
+                        match argument.letter.to_ascii_uppercase() {
+                            'X' | 'Y' | 'Z' | 'I' | 'J' | 'R' | 'F' => {
+                                argument.value = convert_value(argument.value, units, new_units);
+                            }
+                            _ => (),
+                        }
+                    }
+                    new_gcodes.push(converted);
+                }
+                4 | 64 | 90 | 91 | 94 => new_gcodes.push(command),
+                _ => return Err(GctkError::UnsupportedCommand(command)),
+            }
+        }
+
+        line.gcodes = new_gcodes;
     }
     Ok(())
 }
@@ -314,6 +948,30 @@ fn _mesh_level(lines: &mut [Line], _mesh: _Mesh, _num_neighbors: usize) -> Resul
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Treat any parse diagnostic (malformed words, stray arguments, unexpected
+    /// line numbers, ...) as a hard error; diagnostics are still printed to
+    /// stderr first, but processing stops instead of continuing
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Units that numeric offsets on the command line (translate/mirror) are
+    /// given in, used when converting them into a command's active G20/G21 units
+    #[arg(long, global = true, value_enum, default_value_t = UnitsArg::Mm)]
+    units: UnitsArg,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum UnitsArg {
+    Mm,
+    In,
+}
+
+impl From<UnitsArg> for Units {
+    fn from(value: UnitsArg) -> Self {
+        match value {
+            UnitsArg::Mm => Units::Millimeters,
+            UnitsArg::In => Units::Inches,
+        }
+    }
 }
 
 #[derive(Debug, Subcommand)]
@@ -331,6 +989,25 @@ enum Commands {
         #[command(flatten)]
         mirror_axis: MirrorArgGroup,
     },
+    Scale {
+        #[arg(short, default_value_t = 1., allow_negative_numbers = true)]
+        x: f32,
+        #[arg(short, default_value_t = 1., allow_negative_numbers = true)]
+        y: f32,
+        #[arg(short, default_value_t = 1., allow_negative_numbers = true)]
+        z: f32,
+    },
+    Level {
+        /// Path to a JSON file containing an array of `{"x", "y", "z"}` probe points
+        mesh: PathBuf,
+        #[arg(long, default_value_t = 4)]
+        num_neighbors: usize,
+        #[arg(long, default_value_t = 2.0)]
+        power: f32,
+        #[arg(long, default_value_t = 10.0)]
+        segment_length: f32,
+    },
+    ConvertUnits,
 }
 
 #[derive(Args, Debug)]
@@ -345,11 +1022,8 @@ struct MirrorArgGroup {
 }
 
 fn print_lines(lines: &[Line]) {
-    // TODO impl Display for Line
     for line in lines.iter() {
-        for command in line.gcodes.iter() {
-            println!("{}", command);
-        }
+        println!("{line}");
     }
 }
 
@@ -360,28 +1034,255 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Read input
     let mut src = String::new();
     stdin().read_to_string(&mut src)?;
-    let callbacks = GcodeError;
-    let mut lines: Vec<Line> = full_parse_with_callbacks(&src, callbacks).collect();
+    let diagnostics = Diagnostics::new(&src);
+    let raw_lines: Vec<GcodeLine> = full_parse_with_callbacks(&src, diagnostics.clone()).collect();
+
+    let collected_diagnostics = diagnostics.take_diagnostics();
+    if !collected_diagnostics.is_empty() {
+        for diagnostic in &collected_diagnostics {
+            eprintln!("warning: {diagnostic}");
+        }
+        if args.strict {
+            return Err(format!(
+                "found {} parse diagnostic(s) and --strict was passed",
+                collected_diagnostics.len()
+            )
+            .into());
+        }
+    }
+
+    let mut lines: Vec<Line> = raw_lines
+        .into_iter()
+        .map(|raw| Line {
+            line_number: raw.line_number().map(|word| word.value as u32),
+            comments: line_comments(raw.comments()),
+            gcodes: raw.gcodes,
+        })
+        .collect();
 
     // Apply transformation and print output
+    let offset_units: Units = args.units.into();
     match args.command {
         Commands::GetExtent => {
             let extent = get_xy_extent(&lines)?;
             println!("{}", serde_json::to_string(&extent)?);
         }
         Commands::Translate { x, y, z } => {
-            translate(&mut lines, &Point3 { x, y, z })?;
+            translate(&mut lines, &Point3 { x, y, z }, offset_units)?;
             print_lines(&lines);
         }
         Commands::Mirror { mirror_axis } => {
             match (mirror_axis.x, mirror_axis.y, mirror_axis.z) {
-                (Some(x), _, _) => mirror(&mut lines, MirrorAxis::X, x)?,
-                (_, Some(y), _) => mirror(&mut lines, MirrorAxis::Y, y)?,
-                (_, _, Some(z)) => mirror(&mut lines, MirrorAxis::Z, z)?,
+                (Some(x), _, _) => mirror(&mut lines, MirrorAxis::X, x, offset_units)?,
+                (_, Some(y), _) => mirror(&mut lines, MirrorAxis::Y, y, offset_units)?,
+                (_, _, Some(z)) => mirror(&mut lines, MirrorAxis::Z, z, offset_units)?,
                 (None, None, None) => unreachable!("All mirror arguments are None"),
             };
             print_lines(&lines);
         }
+        Commands::Scale { x, y, z } => {
+            scale(&mut lines, &Point3 { x, y, z })?;
+            print_lines(&lines);
+        }
+        Commands::Level {
+            mesh,
+            num_neighbors,
+            power,
+            segment_length,
+        } => {
+            let mesh_src = std::fs::read_to_string(mesh)?;
+            let mesh: Mesh = serde_json::from_str(&mesh_src)?;
+            mesh_level(&mut lines, &mesh, num_neighbors, power, segment_length)?;
+            print_lines(&lines);
+        }
+        Commands::ConvertUnits => {
+            convert_units(&mut lines)?;
+            print_lines(&lines);
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gcode(major: u32, args: &[(char, f32)]) -> GCode {
+        let mut command = GCode::new(Mnemonic::General, major as f32, Span::PLACEHOLDER);
+        for &(letter, value) in args {
+            command
+                .push_argument(Word::new(letter, value, Span::PLACEHOLDER))
+                .unwrap();
+        }
+        command
+    }
+
+    fn line(gcodes: Vec<GCode>) -> Line {
+        Line {
+            line_number: None,
+            gcodes,
+            comments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn comments_round_trip_from_the_real_parser() {
+        let src = "N10 (hello)\nG1 X1 Y2 (foo) ; trailing comment\n";
+        let diagnostics = Diagnostics::new(src);
+        let raw_lines: Vec<GcodeLine> = full_parse_with_callbacks(src, diagnostics).collect();
+        let lines: Vec<Line> = raw_lines
+            .into_iter()
+            .map(|raw| Line {
+                line_number: raw.line_number().map(|word| word.value as u32),
+                comments: line_comments(raw.comments()),
+                gcodes: raw.gcodes,
+            })
+            .collect();
+
+        assert_eq!(lines[0].line_number, Some(10));
+        assert!(lines[0].gcodes.is_empty());
+        assert!(matches!(&lines[0].comments[..], [LineComment::Parenthetical(t)] if t == "hello"));
+        assert_eq!(lines[0].to_string(), "N10 (hello)");
+        assert_eq!(lines[1].gcodes.len(), 1);
+        assert!(matches!(
+            &lines[1].comments[..],
+            [LineComment::Parenthetical(a), LineComment::LineEnd(b)]
+                if a == "foo" && b == " trailing comment"
+        ));
+    }
+
+    #[test]
+    fn mesh_level_relative_z_hop_leaves_xy_untouched() {
+        // A flat mesh so the compensation offset is 0 everywhere and the
+        // only thing under test is whether X/Y get synthesized.
+        let mesh = vec![ProbePoint { x: 0., y: 0., z: 0. }];
+        let mut lines = vec![
+            line(vec![gcode(90, &[])]),
+            line(vec![gcode(1, &[('X', 100.), ('Y', 100.)])]),
+            line(vec![gcode(91, &[])]),
+            line(vec![gcode(1, &[('Z', 0.2)])]),
+        ];
+        mesh_level(&mut lines, &mesh, 4, 2.0, 10.0).unwrap();
+
+        let hop = &lines[3].gcodes[0];
+        assert_eq!(hop.value_for('X'), None);
+        assert_eq!(hop.value_for('Y'), None);
+        assert_eq!(hop.value_for('Z'), Some(0.2));
+    }
+
+    #[test]
+    fn r_form_arc_takes_the_short_way_on_positive_r() {
+        // G2 from (0,0) to (10,0) with R=10 has two candidate centers,
+        // (5, 8.66) and (5, -8.66); a positive R must pick the one giving
+        // the minor (60deg) arc, which bulges to y=1.34, not the major
+        // (300deg) arc through the other center.
+        let lines = vec![
+            line(vec![gcode(1, &[('X', 0.), ('Y', 0.)])]),
+            line(vec![gcode(2, &[('X', 10.), ('Y', 0.), ('R', 10.)])]),
+        ];
+        let extent = get_xy_extent(&lines).unwrap();
+        assert_eq!(extent.min_x, 0.);
+        assert_eq!(extent.max_x, 10.);
+        assert_eq!(extent.min_y, 0.);
+        assert!((extent.max_y - 1.339_746).abs() < 1e-3, "{}", extent.max_y);
+    }
+
+    #[test]
+    fn convert_units_flips_mode_word_and_scales_values() {
+        let mut lines = vec![
+            line(vec![gcode(21, &[])]),
+            line(vec![gcode(1, &[('X', 25.4)])]),
+        ];
+        convert_units(&mut lines).unwrap();
+        assert_eq!(lines[0].gcodes[0].major_number(), 20);
+        assert!((lines[1].gcodes[0].value_for('X').unwrap() - 1.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn convert_units_rejects_ambiguous_feed_rate_on_units_change_line() {
+        let mut lines = vec![line(vec![
+            gcode(21, &[]),
+            gcode(1, &[('X', 1.), ('F', 100.)]),
+        ])];
+        assert!(matches!(
+            convert_units(&mut lines),
+            Err(GctkError::AmbiguousUnitsConversion(_))
+        ));
+    }
+
+    #[test]
+    fn mesh_level_converts_probe_offset_to_the_active_units() {
+        // Mesh is always mm; with a flat +1mm offset everywhere, a move in
+        // inch mode (G20) should get a Z bump of 1/25.4 inch, not 1mm.
+        let mesh = vec![ProbePoint {
+            x: 0.,
+            y: 0.,
+            z: 1.,
+        }];
+        let mut lines = vec![
+            line(vec![gcode(20, &[])]),
+            line(vec![gcode(90, &[])]),
+            line(vec![gcode(1, &[('X', 0.), ('Y', 0.), ('Z', 0.)])]),
+        ];
+        mesh_level(&mut lines, &mesh, 1, 2.0, 10.0).unwrap();
+        let z = lines[2].gcodes[0].value_for('Z').unwrap();
+        assert!((z - 1. / 25.4).abs() < 1e-4, "{z}");
+    }
+
+    #[test]
+    fn mesh_level_rejects_an_empty_mesh() {
+        let mesh = vec![];
+        let mut lines = vec![line(vec![gcode(1, &[('X', 1.), ('Y', 1.)])])];
+        assert!(matches!(
+            mesh_level(&mut lines, &mesh, 4, 2.0, 10.0),
+            Err(GctkError::EmptyMesh)
+        ));
+    }
+
+    #[test]
+    fn mesh_level_allows_a_z_only_preamble_move_before_any_xy_is_known() {
+        // A pure "G1 Z5 F5000" setup move, with no prior X/Y move, must not
+        // error just because X/Y have never been set.
+        let mesh = vec![ProbePoint { x: 0., y: 0., z: 0. }];
+        let mut lines = vec![line(vec![gcode(1, &[('Z', 5.)])])];
+        mesh_level(&mut lines, &mesh, 4, 2.0, 10.0).unwrap();
+        let preamble = &lines[0].gcodes[0];
+        assert_eq!(preamble.value_for('X'), None);
+        assert_eq!(preamble.value_for('Y'), None);
+        assert_eq!(preamble.value_for('Z'), Some(5.));
+    }
+
+    #[test]
+    fn scale_multiplies_an_arc_uniformly_in_xy() {
+        let factors = Point3 {
+            x: 2.,
+            y: 2.,
+            z: 3.,
+        };
+        let mut lines = vec![line(vec![gcode(
+            2,
+            &[('X', 10.), ('Y', 5.), ('I', 1.), ('J', 2.), ('Z', 4.)],
+        )])];
+        scale(&mut lines, &factors).unwrap();
+        let arc = &lines[0].gcodes[0];
+        assert_eq!(arc.value_for('X'), Some(20.));
+        assert_eq!(arc.value_for('Y'), Some(10.));
+        assert_eq!(arc.value_for('I'), Some(2.));
+        assert_eq!(arc.value_for('J'), Some(4.));
+        assert_eq!(arc.value_for('Z'), Some(12.));
+    }
+
+    #[test]
+    fn scale_rejects_a_non_uniform_xy_factor_on_an_arc() {
+        let factors = Point3 {
+            x: 2.,
+            y: 3.,
+            z: 1.,
+        };
+        let mut lines = vec![line(vec![gcode(3, &[('X', 10.), ('Y', 5.)])])];
+        assert!(matches!(
+            scale(&mut lines, &factors),
+            Err(GctkError::NonUniformArcScale(_))
+        ));
+    }
+}